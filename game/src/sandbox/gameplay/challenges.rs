@@ -0,0 +1,147 @@
+use crate::game::Transition;
+use crate::sandbox::gameplay::faster_trips::TripFilter;
+use crate::sandbox::gameplay::GameplayMode;
+use crate::sandbox::SandboxMode;
+use crate::ui::UI;
+use abstutil::Timer;
+use ezgui::{Choice, EventCtx, Line, Text, Wizard};
+use geom::Duration;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// A single result a gameplay mode can be scored on. Lower is always better, so personal-best
+// comparisons stay uniform; metrics where more is better (like ridership) aren't stored as scores.
+#[derive(Clone, Copy, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub enum Score {
+    Duration(Duration),
+    Count(usize),
+}
+
+impl Score {
+    fn better_than(self, other: Score) -> bool {
+        match (self, other) {
+            (Score::Duration(a), Score::Duration(b)) => a < b,
+            (Score::Count(a), Score::Count(b)) => a < b,
+            _ => false,
+        }
+    }
+}
+
+// Tracks the player's best-ever result for every (map, GameplayMode) pair, persisted to disk so
+// progress survives between sessions.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ChallengeProgress {
+    // Keyed by map name, then by GameplayMode::storage_key().
+    best_scores: BTreeMap<String, BTreeMap<String, Score>>,
+}
+
+impl ChallengeProgress {
+    pub fn new() -> ChallengeProgress {
+        ChallengeProgress {
+            best_scores: BTreeMap::new(),
+        }
+    }
+
+    pub fn load() -> ChallengeProgress {
+        abstutil::read_binary(&abstutil::path_player_progress(), &mut Timer::throwaway())
+            .unwrap_or_else(|_| ChallengeProgress::new())
+    }
+
+    pub fn save(&self) {
+        abstutil::write_binary(abstutil::path_player_progress(), self);
+    }
+
+    // Returns true if this result beats the stored personal best (which is then updated and
+    // persisted).
+    pub fn update_if_better(&mut self, map_name: &str, mode: &GameplayMode, score: Score) -> bool {
+        let per_map = self
+            .best_scores
+            .entry(map_name.to_string())
+            .or_insert_with(BTreeMap::new);
+        let key = mode.storage_key();
+        let better = match per_map.get(&key) {
+            Some(best) => score.better_than(*best),
+            None => true,
+        };
+        if better {
+            per_map.insert(key, score);
+            self.save();
+        }
+        better
+    }
+
+    pub fn best_score(&self, map_name: &str, mode: &GameplayMode) -> Option<Score> {
+        self.best_scores
+            .get(map_name)
+            .and_then(|m| m.get(&mode.storage_key()))
+            .cloned()
+    }
+
+    pub fn status_line(&self, map_name: &str, mode: &GameplayMode) -> Text {
+        let mut txt = Text::new();
+        match self.best_score(map_name, mode) {
+            Some(Score::Duration(d)) => {
+                txt.add(Line(format!("Personal best: {}", d.minimal_tostring())))
+            }
+            Some(Score::Count(n)) => txt.add(Line(format!("Personal best: {}", n))),
+            None => txt.add(Line("Not attempted yet")),
+        }
+        txt
+    }
+}
+
+// Every mode the player can pick from the campaign menu, with a human label and the mode it starts.
+// Order matters: each challenge unlocks once the one before it has been completed.
+fn all_challenges() -> Vec<(String, GameplayMode)> {
+    vec![
+        ("Gridlock city".to_string(), GameplayMode::CreateGridlock),
+        (
+            "Optimize a bus route".to_string(),
+            GameplayMode::OptimizeBus("49".to_string()),
+        ),
+        ("Contain the Outbreak".to_string(), GameplayMode::Pandemic),
+        (
+            "Congestion Pricing".to_string(),
+            GameplayMode::CongestionPricing,
+        ),
+        (
+            "Faster Trips".to_string(),
+            GameplayMode::FasterTrips(TripFilter::all_modes()),
+        ),
+    ]
+}
+
+// A menu that lists every challenge with locked/unlocked/completed status, so players progress
+// through a campaign instead of picking a mode ad hoc. A challenge is locked until the one
+// before it in all_challenges() has a recorded personal best.
+pub fn pick_challenge(
+    wiz: &mut Wizard,
+    ctx: &mut EventCtx,
+    ui: &mut UI,
+    progress: &ChallengeProgress,
+) -> Option<Transition> {
+    let map_name = ui.primary.map.get_name().clone();
+    let progress = progress.clone();
+    let mode = wiz.wrap(ctx).choose("Pick a challenge", || {
+        let challenges = all_challenges();
+        let mut previous_completed = true;
+        challenges
+            .into_iter()
+            .map(|(label, mode)| {
+                let completed = progress.best_score(&map_name, &mode).is_some();
+                let status = if completed {
+                    "completed"
+                } else if previous_completed {
+                    "unlocked"
+                } else {
+                    "locked"
+                };
+                previous_completed = completed;
+                Choice::new(format!("{} ({})", label, status), mode).active(status != "locked")
+            })
+            .collect()
+    })?;
+    Some(Transition::Replace(Box::new(SandboxMode::new(
+        ctx, ui, mode,
+    ))))
+}
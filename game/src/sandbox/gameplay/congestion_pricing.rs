@@ -0,0 +1,189 @@
+use crate::game::Transition;
+use crate::sandbox::gameplay::{
+    cmp_count_more, cmp_duration_shorter, cmp_windowed_more, Score, State, WINDOW_LENGTH,
+};
+use crate::ui::UI;
+use ezgui::{hotkey, EventCtx, GfxCtx, Key, Line, ModalMenu, Text, Wizard};
+use geom::{Duration, Time};
+use map_model::RoadID;
+use sim::Analytics;
+use std::collections::BTreeMap;
+
+// How far back to look when deciding whether a road is currently over its cap.
+const THROUGHPUT_WINDOW: Duration = Duration::const_seconds(15.0 * 60.0);
+// How long a diverted agent is held at the road's entrance before retrying.
+const ENTRANCE_DELAY: Duration = Duration::const_seconds(30.0);
+
+pub struct CongestionPricing {
+    // Vehicles per THROUGHPUT_WINDOW allowed onto each capped road. Roads absent here are
+    // uncapped.
+    caps: BTreeMap<RoadID, usize>,
+    // Recent entry times per road, used to check the rolling inflow against its cap.
+    recent_entries: BTreeMap<RoadID, Vec<Time>>,
+    diverted: usize,
+    // The in-progress "set a road's cap" prompt, carried across event() calls the same way
+    // OptimizeBus::new_route threads its wizard.
+    setting_cap: Option<Wizard>,
+    // Sim time of the last tick this mode enforced caps for. event() fires once per UI frame, not
+    // once per sim tick, so this gates enforce_caps on time actually advancing, the same way
+    // mod.rs's manage_overlays gates on time_changed.
+    last_tick: Option<Time>,
+}
+
+impl CongestionPricing {
+    pub fn new(ctx: &mut EventCtx, ui: &UI) -> (ModalMenu, State) {
+        let menu = ModalMenu::new(
+            "Congestion Pricing",
+            vec![vec![
+                (hotkey(Key::C), "set a road's cap"),
+                (hotkey(Key::Tab), "pick a challenge"),
+            ]],
+            ctx,
+        );
+        let _ = ui;
+        (
+            menu,
+            State::CongestionPricing(CongestionPricing {
+                caps: BTreeMap::new(),
+                recent_entries: BTreeMap::new(),
+                diverted: 0,
+                setting_cap: None,
+                last_tick: None,
+            }),
+        )
+    }
+
+    // Call whenever an agent is newly routed onto `road`. Returns true if the road's cap is
+    // exceeded and the agent should be held at the entrance instead.
+    fn over_cap(&mut self, road: RoadID, now: Time) -> bool {
+        let cap = match self.caps.get(&road) {
+            Some(c) => *c,
+            None => return false,
+        };
+        let entries = self.recent_entries.entry(road).or_insert_with(Vec::new);
+        entries.retain(|t| now - *t <= THROUGHPUT_WINDOW);
+        if entries.len() >= cap {
+            self.diverted += 1;
+            true
+        } else {
+            entries.push(now);
+            false
+        }
+    }
+
+    // Walks every agent about to enter a capped road this tick and enforces the cap by holding
+    // whoever's over it at the entrance for ENTRANCE_DELAY, rather than just tallying a throughput
+    // stat after the fact. Agents aren't rerouted onto other roads.
+    fn enforce_caps(&mut self, ui: &mut UI, now: Time) {
+        if self.caps.is_empty() {
+            return;
+        }
+        for (agent, road) in ui.primary.sim.get_agents_entering_border(&ui.primary.map) {
+            if self.over_cap(road, now) {
+                ui.primary
+                    .sim
+                    .delay_agent_at_border(agent, ENTRANCE_DELAY, &ui.primary.map);
+            }
+        }
+    }
+
+    // Shorter median trip duration is better; per-trip delay isn't measured separately. Throughput
+    // is surfaced separately in the menu since ChallengeProgress only tracks "lower is better"
+    // scores.
+    pub fn current_score(&self, ui: &UI) -> Score {
+        Score::Duration(ui.primary.sim.get_analytics().median_trip_duration())
+    }
+
+    pub fn event(
+        &mut self,
+        ctx: &mut EventCtx,
+        ui: &mut UI,
+        menu: &mut ModalMenu,
+        prebaked: &Analytics,
+    ) -> Option<Transition> {
+        menu.event(ctx);
+        if self.setting_cap.is_none() && menu.action("set a road's cap") {
+            self.setting_cap = Some(Wizard::new());
+        }
+        if let Some(mut wizard) = self.setting_cap.take() {
+            if set_cap(&mut wizard, ctx, ui, &mut self.caps).is_some() {
+                // Finished this round of picking a road and a cap.
+            } else if wizard.aborted() {
+                // Player backed out; nothing to do.
+            } else {
+                self.setting_cap = Some(wizard);
+            }
+        }
+
+        let now = ui.primary.sim.time();
+        if self.last_tick != Some(now) {
+            self.enforce_caps(ui, now);
+            self.last_tick = Some(now);
+        }
+
+        let now_throughput = ui.primary.sim.get_analytics().finished_trip_count();
+        let baseline_throughput = prebaked.finished_trip_count();
+        let now_delay = ui.primary.sim.get_analytics().median_trip_duration();
+        let baseline_delay = prebaked.median_trip_duration();
+        let now_windowed_throughput =
+            windowed_finished_trips(ui.primary.sim.get_analytics(), now, WINDOW_LENGTH) as f64;
+        let baseline_windowed_throughput =
+            windowed_finished_trips(prebaked, now, WINDOW_LENGTH) as f64;
+
+        let mut txt = Text::prompt("Congestion Pricing");
+        txt.add(Line(format!("Roads capped: {}", self.caps.len())));
+        txt.add(Line(format!("Agents diverted so far: {}", self.diverted)));
+        txt.add(Line("Person-throughput: "));
+        txt.append_all(vec![cmp_count_more(now_throughput, baseline_throughput)]);
+        txt.add(Line("Median trip duration: "));
+        txt.append_all(cmp_duration_shorter(now_delay, baseline_delay));
+        txt.add(Line(format!(
+            "Throughput in last {}: ",
+            WINDOW_LENGTH.minimal_tostring()
+        )));
+        txt.add(cmp_windowed_more(
+            now_windowed_throughput,
+            baseline_windowed_throughput,
+        ));
+
+        menu.set_info(ctx, txt);
+        None
+    }
+
+    pub fn draw(&self, _g: &mut GfxCtx) {}
+}
+
+fn set_cap(
+    wiz: &mut Wizard,
+    ctx: &mut EventCtx,
+    ui: &UI,
+    caps: &mut BTreeMap<RoadID, usize>,
+) -> Option<()> {
+    let road = wiz.wrap(ctx).choose_something_no_keys::<RoadID>(
+        "Cap which road?",
+        Box::new(|| {
+            ui.primary
+                .map
+                .all_roads()
+                .iter()
+                .map(|r| (r.id.to_string(), r.id))
+                .collect()
+        }),
+    )?;
+    let cap = wiz.wrap(ctx).input_usize(&format!(
+        "Vehicles per {} allowed on this road?",
+        THROUGHPUT_WINDOW.minimal_tostring()
+    ))?;
+    caps.insert(road, cap);
+    Some(())
+}
+
+// How many trips finished in the window ending at `now`, replayed from an Analytics (either the
+// live sim's or the completed prebaked baseline's).
+fn windowed_finished_trips(analytics: &Analytics, now: Time, horizon: Duration) -> usize {
+    analytics
+        .get_trip_finish_times()
+        .into_iter()
+        .filter(|t| *t <= now && now - *t <= horizon)
+        .count()
+}
@@ -0,0 +1,87 @@
+use crate::game::Transition;
+use crate::sandbox::gameplay::{
+    cmp_count_fewer, cmp_windowed_fewer, Score, State, WindowedSeries, WINDOW_LENGTH,
+};
+use crate::ui::UI;
+use ezgui::{hotkey, Color, EventCtx, GfxCtx, Key, Line, ModalMenu, Text};
+use geom::{Duration, Time};
+use sim::Analytics;
+
+// Challenge mode: keep traffic moving under pressure. Scored on how many intersections end up
+// gridlocked, fewest is better, matching ChallengeProgress's convention everywhere else.
+pub struct CreateGridlock {
+    // Gridlocked-intersection count sampled every tick, over the last WINDOW_LENGTH, to show
+    // whether gridlock is currently getting worse or better rather than just the live snapshot.
+    recent_gridlock: WindowedSeries,
+}
+
+impl CreateGridlock {
+    pub fn new(ctx: &mut EventCtx) -> (ModalMenu, State) {
+        let menu = ModalMenu::new(
+            "Create Gridlock",
+            vec![vec![(hotkey(Key::Tab), "pick a challenge")]],
+            ctx,
+        );
+        (
+            menu,
+            State::CreateGridlock(CreateGridlock {
+                recent_gridlock: WindowedSeries::new(WINDOW_LENGTH),
+            }),
+        )
+    }
+
+    // Fewest gridlocked intersections is better.
+    pub fn current_score(&self, ui: &UI) -> Score {
+        Score::Count(ui.primary.sim.count_gridlocked_intersections())
+    }
+
+    pub fn event(
+        &mut self,
+        ctx: &mut EventCtx,
+        ui: &mut UI,
+        menu: &mut ModalMenu,
+        prebaked: &Analytics,
+    ) -> Option<Transition> {
+        menu.event(ctx);
+
+        let now = ui.primary.sim.time();
+        let now_gridlocked = ui.primary.sim.count_gridlocked_intersections();
+        let baseline_gridlocked = prebaked.count_gridlocked_intersections();
+        self.recent_gridlock.add(now, now_gridlocked as f64);
+
+        let mut txt = Text::prompt("Create Gridlock");
+        txt.add(Line(format!("Gridlocked intersections: {}", now_gridlocked)).fg(Color::RED));
+        txt.add_appended(vec![
+            Line("Compared to baseline: "),
+            cmp_count_fewer(now_gridlocked, baseline_gridlocked),
+        ]);
+        txt.add(Line(format!(
+            "In last {}: ",
+            WINDOW_LENGTH.minimal_tostring()
+        )));
+        txt.add(cmp_windowed_fewer(
+            self.recent_gridlock.avg(),
+            windowed_gridlock_avg(prebaked, now, WINDOW_LENGTH),
+        ));
+
+        menu.set_info(ctx, txt);
+        None
+    }
+
+    pub fn draw(&self, _g: &mut GfxCtx) {}
+}
+
+// Average gridlocked-intersection count over the window ending at `now`, replayed from an
+// Analytics (either the live sim's or the completed prebaked baseline's).
+fn windowed_gridlock_avg(analytics: &Analytics, now: Time, horizon: Duration) -> f64 {
+    let samples: Vec<f64> = analytics
+        .get_gridlock_samples()
+        .into_iter()
+        .filter(|(t, _)| *t <= now && now - *t <= horizon)
+        .map(|(_, count)| count as f64)
+        .collect();
+    if samples.is_empty() {
+        return 0.0;
+    }
+    samples.iter().sum::<f64>() / (samples.len() as f64)
+}
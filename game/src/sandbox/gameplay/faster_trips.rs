@@ -0,0 +1,382 @@
+use crate::game::Transition;
+use crate::sandbox::gameplay::{
+    cmp_duration_shorter, cmp_windowed_fewer, Score, State, WINDOW_LENGTH,
+};
+use crate::ui::UI;
+use ezgui::{hotkey, Choice, EventCtx, GfxCtx, Key, Line, ModalMenu, Text, Wizard};
+use geom::{Distance, Duration, Time};
+use map_model::BuildingID;
+use sim::{Analytics, TripID, TripMode};
+use std::collections::BTreeSet;
+
+// A composable set of predicates narrowing down which trips to score. A trip must satisfy every
+// predicate that's Some to count towards the comparison.
+#[derive(Clone)]
+pub struct TripFilter {
+    modes: BTreeSet<TripMode>,
+    departure_time: Option<(Time, Time)>,
+    trip_length: Option<(Distance, Distance)>,
+    starts_from: Option<BuildingID>,
+    ends_at: Option<BuildingID>,
+}
+
+impl TripFilter {
+    pub fn all_modes() -> TripFilter {
+        TripFilter {
+            modes: vec![
+                TripMode::Walk,
+                TripMode::Bike,
+                TripMode::Transit,
+                TripMode::Drive,
+            ]
+            .into_iter()
+            .collect(),
+            departure_time: None,
+            trip_length: None,
+            starts_from: None,
+            ends_at: None,
+        }
+    }
+
+    pub fn just_mode(mode: TripMode) -> TripFilter {
+        let mut modes = BTreeSet::new();
+        modes.insert(mode);
+        TripFilter {
+            modes,
+            departure_time: None,
+            trip_length: None,
+            starts_from: None,
+            ends_at: None,
+        }
+    }
+
+    fn matches(&self, ui: &UI, trip: TripID) -> bool {
+        let info = ui.primary.sim.trip_info(trip);
+        if !self.modes.contains(&info.mode) {
+            return false;
+        }
+        if let Some((start, end)) = self.departure_time {
+            if info.departure < start || info.departure >= end {
+                return false;
+            }
+        }
+        if let Some((lo, hi)) = self.trip_length {
+            if info.trip_length < lo || info.trip_length > hi {
+                return false;
+            }
+        }
+        if let Some(b) = self.starts_from {
+            if info.start_bldg != Some(b) {
+                return false;
+            }
+        }
+        if let Some(b) = self.ends_at {
+            if info.end_bldg != Some(b) {
+                return false;
+            }
+        }
+        true
+    }
+
+    // Walks the player through picking every predicate, defaulting to "don't care" when they
+    // skip a step. Mirrors change_scenario's wizard flow. Returns None until every step (modes,
+    // departure window, trip length, origin, destination) has either been answered or declined.
+    pub fn choose(wiz: &mut Wizard, ctx: &mut EventCtx, ui: &UI) -> Option<TripFilter> {
+        let mut w = wiz.wrap(ctx);
+        let mut modes = BTreeSet::new();
+        loop {
+            let remaining: Vec<TripMode> = vec![
+                TripMode::Walk,
+                TripMode::Bike,
+                TripMode::Transit,
+                TripMode::Drive,
+            ]
+            .into_iter()
+            .filter(|m| !modes.contains(m))
+            .collect();
+            if remaining.is_empty() {
+                break;
+            }
+            let prompt = "Add which trip mode to the filter?";
+            match w.choose(prompt, || {
+                let mut choices: Vec<Choice<Option<TripMode>>> = remaining
+                    .iter()
+                    .map(|m| Choice::new(format!("{:?}", m), Some(*m)))
+                    .collect();
+                choices.push(Choice::new("done adding modes", None));
+                choices
+            })? {
+                Some(m) => {
+                    modes.insert(m);
+                }
+                None => break,
+            }
+        }
+        if modes.is_empty() {
+            modes = vec![
+                TripMode::Walk,
+                TripMode::Bike,
+                TripMode::Transit,
+                TripMode::Drive,
+            ]
+            .into_iter()
+            .collect();
+        }
+
+        let departure_time = if w.choose_string("Restrict to a departure time window?", || {
+            vec!["no".to_string(), "yes".to_string()]
+        })? == "yes"
+        {
+            let start_mins = w.input_usize("Departure window starts how many minutes after midnight?")?;
+            let end_mins = w.input_usize("Departure window ends how many minutes after midnight?")?;
+            Some((
+                Time::START_OF_DAY + Duration::minutes(start_mins),
+                Time::START_OF_DAY + Duration::minutes(end_mins),
+            ))
+        } else {
+            None
+        };
+
+        let trip_length = if w.choose_string("Restrict to a trip length range?", || {
+            vec!["no".to_string(), "yes".to_string()]
+        })? == "yes"
+        {
+            let lo_meters = w.input_usize("Shortest trip length, in meters?")?;
+            let hi_meters = w.input_usize("Longest trip length, in meters?")?;
+            Some((
+                Distance::meters(lo_meters as f64),
+                Distance::meters(hi_meters as f64),
+            ))
+        } else {
+            None
+        };
+
+        let starts_from = if w.choose_string("Restrict to trips starting from one building?", || {
+            vec!["no".to_string(), "yes".to_string()]
+        })? == "yes"
+        {
+            Some(
+                w.choose_something_no_keys::<BuildingID>(
+                    "Starting building?",
+                    Box::new(|| {
+                        ui.primary
+                            .map
+                            .all_buildings()
+                            .iter()
+                            .map(|b| (b.id.to_string(), b.id))
+                            .collect()
+                    }),
+                )?,
+            )
+        } else {
+            None
+        };
+
+        let ends_at = if w.choose_string("Restrict to trips ending at one building?", || {
+            vec!["no".to_string(), "yes".to_string()]
+        })? == "yes"
+        {
+            Some(
+                w.choose_something_no_keys::<BuildingID>(
+                    "Ending building?",
+                    Box::new(|| {
+                        ui.primary
+                            .map
+                            .all_buildings()
+                            .iter()
+                            .map(|b| (b.id.to_string(), b.id))
+                            .collect()
+                    }),
+                )?,
+            )
+        } else {
+            None
+        };
+
+        Some(TripFilter {
+            modes,
+            departure_time,
+            trip_length,
+            starts_from,
+            ends_at,
+        })
+    }
+
+    // A stable string for ChallengeProgress's storage key. Doesn't need to be pretty, just
+    // unique per distinct filter.
+    pub fn storage_key(&self) -> String {
+        format!(
+            "{:?}|{:?}|{:?}|{:?}|{:?}",
+            self.modes, self.departure_time, self.trip_length, self.starts_from, self.ends_at
+        )
+    }
+
+    fn describe(&self) -> String {
+        format!(
+            "{} trips{}{}{}",
+            self.modes
+                .iter()
+                .map(|m| format!("{:?}", m))
+                .collect::<Vec<_>>()
+                .join("/"),
+            if self.departure_time.is_some() {
+                " in a departure window"
+            } else {
+                ""
+            },
+            if self.trip_length.is_some() {
+                " in a trip length range"
+            } else {
+                ""
+            },
+            if self.starts_from.is_some() || self.ends_at.is_some() {
+                " in a specific area"
+            } else {
+                ""
+            },
+        )
+    }
+}
+
+pub struct FasterTrips {
+    filter: TripFilter,
+    // The in-progress "change trip filter" prompt, carried across event() calls the same way
+    // CongestionPricing::setting_cap threads its wizard.
+    changing_filter: Option<Wizard>,
+}
+
+impl FasterTrips {
+    pub fn new(filter: TripFilter, ctx: &mut EventCtx) -> (ModalMenu, State) {
+        let menu = ModalMenu::new(
+            "Faster Trips",
+            vec![vec![
+                (hotkey(Key::F), "change trip filter"),
+                (hotkey(Key::Tab), "pick a challenge"),
+            ]],
+            ctx,
+        );
+        (
+            menu,
+            State::FasterTrips(FasterTrips {
+                filter,
+                changing_filter: None,
+            }),
+        )
+    }
+
+    // Average duration of matching trips, fewer is better. Averaging instead of summing means
+    // this tracks how matching trips are actually going as the day progresses, rather than a
+    // running total that can only grow and would permanently lock in whatever the very first
+    // frame happened to read as the "personal best".
+    pub fn current_score(&self, ui: &UI) -> Score {
+        Score::Duration(average_matching_trip_duration(
+            &self.filter,
+            ui,
+            ui.primary.sim.get_analytics(),
+        ))
+    }
+
+    pub fn event(
+        &mut self,
+        ctx: &mut EventCtx,
+        ui: &mut UI,
+        menu: &mut ModalMenu,
+        prebaked: &Analytics,
+    ) -> Option<Transition> {
+        menu.event(ctx);
+        if self.changing_filter.is_none() && menu.action("change trip filter") {
+            self.changing_filter = Some(Wizard::new());
+        }
+        if let Some(mut wizard) = self.changing_filter.take() {
+            if let Some(filter) = TripFilter::choose(&mut wizard, ctx, ui) {
+                self.filter = filter;
+            } else if wizard.aborted() {
+                // Player backed out; keep the existing filter.
+            } else {
+                self.changing_filter = Some(wizard);
+            }
+        }
+
+        let sim_now = ui.primary.sim.time();
+        let now = matching_trip_duration(&self.filter, ui, ui.primary.sim.get_analytics());
+        let baseline = matching_trip_duration(&self.filter, ui, prebaked);
+
+        let mut txt = Text::prompt("Faster Trips");
+        txt.add(Line(format!("Filter: {}", self.filter.describe())));
+        txt.add(Line("Aggregate duration: "));
+        txt.append_all(cmp_duration_shorter(now, baseline));
+        txt.add(Line(format!(
+            "In last {}: ",
+            WINDOW_LENGTH.minimal_tostring()
+        )));
+        txt.add(cmp_windowed_fewer(
+            windowed_matching_trip_duration(
+                &self.filter,
+                ui,
+                ui.primary.sim.get_analytics(),
+                sim_now,
+                WINDOW_LENGTH,
+            )
+            .inner_seconds(),
+            windowed_matching_trip_duration(&self.filter, ui, prebaked, sim_now, WINDOW_LENGTH)
+                .inner_seconds(),
+        ));
+
+        menu.set_info(ctx, txt);
+        None
+    }
+
+    pub fn draw(&self, _g: &mut GfxCtx) {}
+}
+
+// Sums trip duration over every trip matching the filter, using whichever Analytics (live or
+// prebaked) the caller wants to score against.
+fn matching_trip_duration(filter: &TripFilter, ui: &UI, analytics: &Analytics) -> Duration {
+    let mut total = Duration::ZERO;
+    for (trip, dt) in analytics.get_all_trip_times() {
+        if filter.matches(ui, trip) {
+            total += dt;
+        }
+    }
+    total
+}
+
+// Average duration of matching trips finished so far, using whichever Analytics (live or
+// prebaked) the caller wants to score against.
+fn average_matching_trip_duration(filter: &TripFilter, ui: &UI, analytics: &Analytics) -> Duration {
+    let mut total = Duration::ZERO;
+    let mut count = 0;
+    for (trip, dt) in analytics.get_all_trip_times() {
+        if filter.matches(ui, trip) {
+            total += dt;
+            count += 1;
+        }
+    }
+    if count == 0 {
+        Duration::ZERO
+    } else {
+        total / (count as f64)
+    }
+}
+
+// Same as matching_trip_duration, but only counting trips that finished in the window ending at
+// `now` -- lets the windowed comparison reflect recent trips instead of the whole day so far.
+fn windowed_matching_trip_duration(
+    filter: &TripFilter,
+    ui: &UI,
+    analytics: &Analytics,
+    now: Time,
+    horizon: Duration,
+) -> Duration {
+    let mut total = Duration::ZERO;
+    for (trip, dt) in analytics.get_all_trip_times() {
+        if !filter.matches(ui, trip) {
+            continue;
+        }
+        let finish = ui.primary.sim.trip_info(trip).departure + dt;
+        if finish <= now && now - finish <= horizon {
+            total += dt;
+        }
+    }
+    total
+}
@@ -1,24 +1,36 @@
+mod challenges;
+mod congestion_pricing;
 mod create_gridlock;
 mod faster_trips;
 mod freeform;
 mod optimize_bus;
+mod pandemic;
 mod play_scenario;
 
 use crate::game::Transition;
 use crate::render::AgentColorScheme;
+use crate::sandbox::gameplay::faster_trips::TripFilter;
 use crate::sandbox::overlays::Overlays;
 use crate::sandbox::SandboxMode;
 use crate::ui::UI;
 use abstutil::{prettyprint_usize, Timer};
 use ezgui::{Color, EventCtx, GfxCtx, Line, ModalMenu, TextSpan, Wizard};
-use geom::Duration;
-use sim::{Analytics, Scenario, TripMode};
+use geom::{Duration, Time};
+use sim::{Analytics, Scenario};
+use std::collections::VecDeque;
+
+pub use self::challenges::{ChallengeProgress, Score};
 
 pub struct GameplayRunner {
     pub mode: GameplayMode,
     pub menu: ModalMenu,
     state: State,
     prebaked: Analytics,
+    challenges: ChallengeProgress,
+    // The in-progress "pick a challenge" prompt, carried across event() calls the same way
+    // CongestionPricing::setting_cap threads its wizard. Lives here (not on a particular State)
+    // since every mode's menu can open the campaign picker.
+    picking_challenge: Option<Wizard>,
 }
 
 #[derive(Clone)]
@@ -29,8 +41,25 @@ pub enum GameplayMode {
     // Route name
     OptimizeBus(String),
     CreateGridlock,
-    // TODO Be able to filter population by more factors
-    FasterTrips(TripMode),
+    FasterTrips(TripFilter),
+    Pandemic,
+    CongestionPricing,
+}
+
+impl GameplayMode {
+    // A stable string identifying this mode (and its parameters), used as the key for storing
+    // personal-best challenge progress.
+    fn storage_key(&self) -> String {
+        match self {
+            GameplayMode::Freeform => "Freeform".to_string(),
+            GameplayMode::PlayScenario(name) => format!("PlayScenario({})", name),
+            GameplayMode::OptimizeBus(name) => format!("OptimizeBus({})", name),
+            GameplayMode::CreateGridlock => "CreateGridlock".to_string(),
+            GameplayMode::FasterTrips(filter) => format!("FasterTrips({})", filter.storage_key()),
+            GameplayMode::Pandemic => "Pandemic".to_string(),
+            GameplayMode::CongestionPricing => "CongestionPricing".to_string(),
+        }
+    }
 }
 
 pub enum State {
@@ -39,6 +68,8 @@ pub enum State {
     OptimizeBus(optimize_bus::OptimizeBus),
     CreateGridlock(create_gridlock::CreateGridlock),
     FasterTrips(faster_trips::FasterTrips),
+    Pandemic(pandemic::Pandemic),
+    CongestionPricing(congestion_pricing::CongestionPricing),
 }
 
 impl GameplayRunner {
@@ -66,8 +97,16 @@ impl GameplayRunner {
                 create_gridlock::CreateGridlock::new(ctx),
                 Some("weekday_typical_traffic_from_psrc".to_string()),
             ),
-            GameplayMode::FasterTrips(trip_mode) => (
-                faster_trips::FasterTrips::new(trip_mode, ctx),
+            GameplayMode::FasterTrips(filter) => (
+                faster_trips::FasterTrips::new(filter, ctx),
+                Some("weekday_typical_traffic_from_psrc".to_string()),
+            ),
+            GameplayMode::Pandemic => (
+                pandemic::Pandemic::new(ctx, ui),
+                Some("weekday_typical_traffic_from_psrc".to_string()),
+            ),
+            GameplayMode::CongestionPricing => (
+                congestion_pricing::CongestionPricing::new(ctx, ui),
                 Some("weekday_typical_traffic_from_psrc".to_string()),
             ),
         };
@@ -76,6 +115,8 @@ impl GameplayRunner {
             menu: menu.disable_standalone_layout(),
             state,
             prebaked,
+            challenges: ChallengeProgress::load(),
+            picking_challenge: None,
         };
         if let Some(scenario_name) = maybe_scenario {
             ctx.loading_screen("instantiate scenario", |_, timer| {
@@ -151,12 +192,72 @@ impl GameplayRunner {
                     return Some(t);
                 }
             }
+            State::Pandemic(ref mut p) => {
+                if let Some(t) = p.event(ctx, ui, &mut self.menu, &self.prebaked) {
+                    return Some(t);
+                }
+            }
+            State::CongestionPricing(ref mut c) => {
+                if let Some(t) = c.event(ctx, ui, &mut self.menu, &self.prebaked) {
+                    return Some(t);
+                }
+            }
+        }
+        if self.picking_challenge.is_none() && self.menu.action("pick a challenge") {
+            self.picking_challenge = Some(Wizard::new());
+        }
+        if let Some(mut wizard) = self.picking_challenge.take() {
+            if let Some(t) = challenges::pick_challenge(&mut wizard, ctx, ui, &self.challenges) {
+                return Some(t);
+            } else if wizard.aborted() {
+                // Player backed out; stay in the current challenge.
+            } else {
+                self.picking_challenge = Some(wizard);
+            }
+        }
+        if let State::OptimizeBus(ref o) = self.state {
+            let map_name = ui.primary.map.get_name().clone();
+            let score = o.current_score(ui);
+            self.record_score(&map_name, score);
+        }
+        if let State::CreateGridlock(ref g) = self.state {
+            let map_name = ui.primary.map.get_name().clone();
+            let score = g.current_score(ui);
+            self.record_score(&map_name, score);
+        }
+        if let State::FasterTrips(ref f) = self.state {
+            let map_name = ui.primary.map.get_name().clone();
+            let score = f.current_score(ui);
+            self.record_score(&map_name, score);
+        }
+        if let State::Pandemic(ref p) = self.state {
+            let map_name = ui.primary.map.get_name().clone();
+            let score = p.current_score();
+            self.record_score(&map_name, score);
+        }
+        if let State::CongestionPricing(ref c) = self.state {
+            let map_name = ui.primary.map.get_name().clone();
+            let score = c.current_score(ui);
+            self.record_score(&map_name, score);
         }
         None
     }
 
     pub fn draw(&self, g: &mut GfxCtx) {
         self.menu.draw(g);
+        if let State::Pandemic(ref p) = self.state {
+            p.draw(g);
+        }
+        if let State::CongestionPricing(ref c) = self.state {
+            c.draw(g);
+        }
+    }
+
+    // event() calls this every frame with the active mode's current score; only a genuine
+    // improvement over the stored personal best gets persisted to disk.
+    pub fn record_score(&mut self, map_name: &str, score: Score) -> bool {
+        self.challenges
+            .update_if_better(map_name, &self.mode, score)
     }
 }
 
@@ -303,3 +404,94 @@ fn cmp_count_more(now: usize, baseline: usize) -> TextSpan {
         Line("same as baseline")
     }
 }
+
+// Maintains a ring of (Time, value) samples over a sliding window, so a mode can show how an
+// intervention is performing *right now*, not just cumulatively since midnight.
+pub struct WindowedSeries {
+    horizon: Duration,
+    samples: VecDeque<(Time, f64)>,
+}
+
+impl WindowedSeries {
+    pub fn new(horizon: Duration) -> WindowedSeries {
+        WindowedSeries {
+            horizon,
+            samples: VecDeque::new(),
+        }
+    }
+
+    pub fn add(&mut self, now: Time, value: f64) {
+        // Callers may add once per UI frame; keep only one sample per distinct sim time.
+        if let Some((t, v)) = self.samples.back_mut() {
+            if *t == now {
+                *v = value;
+                return;
+            }
+        }
+        self.samples.push_back((now, value));
+        while let Some((t, _)) = self.samples.front() {
+            if now - *t > self.horizon {
+                self.samples.pop_front();
+            } else {
+                break;
+            }
+        }
+    }
+
+    pub fn avg(&self) -> f64 {
+        if self.samples.is_empty() {
+            return 0.0;
+        }
+        self.samples.iter().map(|(_, v)| v).sum::<f64>() / (self.samples.len() as f64)
+    }
+}
+
+// Fewer is better. Meant to be displayed right alongside cmp_count_fewer, using a windowed
+// average instead of the cumulative total. Callers compute `now` and `baseline` however makes
+// sense for their metric -- a live WindowedSeries::avg() for `now`, and the same window replayed
+// against the prebaked Analytics for `baseline`, so the comparison reflects a real baseline
+// instead of an empty one.
+pub fn cmp_windowed_fewer(now: f64, baseline: f64) -> TextSpan {
+    if now < baseline {
+        Line(format!(
+            "{:.1} fewer in last {}",
+            baseline - now,
+            WINDOW_LENGTH.minimal_tostring()
+        ))
+        .fg(Color::GREEN)
+    } else if now > baseline {
+        Line(format!(
+            "{:.1} more in last {}",
+            now - baseline,
+            WINDOW_LENGTH.minimal_tostring()
+        ))
+        .fg(Color::RED)
+    } else {
+        Line("same as baseline over this window")
+    }
+}
+
+// More is better. Same windowed-vs-baseline shape as cmp_windowed_fewer, for metrics like
+// throughput where a bigger number is the goal.
+pub fn cmp_windowed_more(now: f64, baseline: f64) -> TextSpan {
+    if now < baseline {
+        Line(format!(
+            "{:.1} fewer in last {}",
+            baseline - now,
+            WINDOW_LENGTH.minimal_tostring()
+        ))
+        .fg(Color::RED)
+    } else if now > baseline {
+        Line(format!(
+            "{:.1} more in last {}",
+            now - baseline,
+            WINDOW_LENGTH.minimal_tostring()
+        ))
+        .fg(Color::GREEN)
+    } else {
+        Line("same as baseline over this window")
+    }
+}
+
+// The sliding window used for live scoring overlays.
+const WINDOW_LENGTH: Duration = Duration::const_seconds(15.0 * 60.0);
@@ -0,0 +1,256 @@
+use crate::game::Transition;
+use crate::sandbox::gameplay::{
+    cmp_count_more, cmp_duration_shorter, cmp_windowed_more, Score, State, WINDOW_LENGTH,
+};
+use crate::sandbox::overlays::Overlays;
+use crate::ui::UI;
+use abstutil::prettyprint_usize;
+use ezgui::{hotkey, EventCtx, GfxCtx, Key, Line, ModalMenu, Text, Wizard};
+use geom::{Duration, Time};
+use map_model::{BusRouteID, BusStopID};
+use sim::Analytics;
+
+pub struct OptimizeBus {
+    route_name: String,
+    show_route: bool,
+    // While designing a brand-new route, the ordered stops picked so far and the wizard driving
+    // the stop-picking prompts.
+    new_route: Option<(Vec<BusStopID>, Wizard)>,
+    // Once stop-picking is done, the stops settled on and the wizard prompting for the new
+    // route's headway before it's instantiated.
+    new_route_headway: Option<(Vec<BusStopID>, Wizard)>,
+}
+
+impl OptimizeBus {
+    pub fn new(route_name: String, ctx: &mut EventCtx, ui: &mut UI) -> (ModalMenu, State) {
+        let menu = ModalMenu::new(
+            "Optimize Bus",
+            vec![vec![
+                (hotkey(Key::R), "show/hide route"),
+                (hotkey(Key::N), "design a new route"),
+                (hotkey(Key::Tab), "pick a challenge"),
+            ]],
+            ctx,
+        );
+        let _ = ui;
+        (
+            menu,
+            State::OptimizeBus(OptimizeBus {
+                route_name,
+                show_route: false,
+                new_route: None,
+                new_route_headway: None,
+            }),
+        )
+    }
+
+    // Median passenger wait, fewer is better. Using the median instead of the total wait means
+    // this keeps tracking how the route is actually performing as the day goes on, rather than a
+    // running sum that can only grow and would permanently lock in whatever the very first frame
+    // happened to read as the "personal best".
+    pub fn current_score(&self, ui: &UI) -> Score {
+        let route = ui.primary.map.get_br(&self.route_name);
+        let (_, median_wait, _) = passenger_wait_stats(ui.primary.sim.get_analytics(), route.id);
+        Score::Duration(median_wait)
+    }
+
+    pub fn event(
+        &mut self,
+        ctx: &mut EventCtx,
+        ui: &mut UI,
+        overlays: &mut Overlays,
+        menu: &mut ModalMenu,
+        prebaked: &Analytics,
+    ) -> Option<Transition> {
+        menu.event(ctx);
+        let _ = overlays;
+
+        if menu.action("show/hide route") {
+            self.show_route = !self.show_route;
+        }
+        if self.new_route.is_none() && menu.action("design a new route") {
+            self.new_route = Some((Vec::new(), Wizard::new()));
+        }
+        if let Some((mut stops, mut wizard)) = self.new_route.take() {
+            if let Some(stop) = pick_new_route_stop(&mut wizard, ctx, ui) {
+                stops.push(stop);
+                self.new_route = Some((stops, Wizard::new()));
+            } else if wizard.aborted() {
+                if stops.len() >= 2 {
+                    self.new_route_headway = Some((stops, Wizard::new()));
+                }
+            } else {
+                self.new_route = Some((stops, wizard));
+            }
+        }
+        if let Some((stops, mut wizard)) = self.new_route_headway.take() {
+            if let Some(headway_mins) = pick_new_route_headway(&mut wizard, ctx) {
+                instantiate_new_route(stops, Duration::minutes(headway_mins), ui);
+            } else if wizard.aborted() {
+                // Player backed out; drop the route under design.
+            } else {
+                self.new_route_headway = Some((stops, wizard));
+            }
+        }
+
+        let route = ui.primary.map.get_br(&self.route_name);
+        let now = ui.primary.sim.time();
+        let stop_detail = per_stop_detail(ui.primary.sim.get_analytics(), route.id);
+
+        let mut txt = Text::prompt("Optimize Bus");
+        txt.add(Line(format!("Route: {}", self.route_name)));
+
+        let now_trip_times = ui.primary.sim.get_analytics().bus_arrivals(route.id);
+        let baseline_trip_times = prebaked.bus_arrivals(route.id);
+        txt.add(Line("Headway: "));
+        txt.append_all(cmp_duration_shorter(
+            median_headway(&now_trip_times),
+            median_headway(&baseline_trip_times),
+        ));
+
+        let (now_wait, now_p50, now_p90) =
+            passenger_wait_stats(ui.primary.sim.get_analytics(), route.id);
+        let (base_wait, base_p50, base_p90) = passenger_wait_stats(prebaked, route.id);
+        txt.add(Line(format!(
+            "Total passenger wait time: {}",
+            now_wait.minimal_tostring()
+        )));
+        txt.append_all(cmp_duration_shorter(now_wait, base_wait));
+        txt.add(Line("Median wait: "));
+        txt.append_all(cmp_duration_shorter(now_p50, base_p50));
+        txt.add(Line("90%ile wait: "));
+        txt.append_all(cmp_duration_shorter(now_p90, base_p90));
+
+        let now_ridership = ui.primary.sim.get_analytics().total_boardings(route.id);
+        let baseline_ridership = prebaked.total_boardings(route.id);
+        txt.add(Line(format!(
+            "Ridership: {}",
+            prettyprint_usize(now_ridership)
+        )));
+        txt.append_all(vec![cmp_count_more(now_ridership, baseline_ridership)]);
+        txt.add(Line(format!(
+            "Boardings in last {}: ",
+            WINDOW_LENGTH.minimal_tostring()
+        )));
+        txt.add(cmp_windowed_more(
+            windowed_boardings(ui.primary.sim.get_analytics(), route.id, now, WINDOW_LENGTH) as f64,
+            windowed_boardings(prebaked, route.id, now, WINDOW_LENGTH) as f64,
+        ));
+
+        txt.add(Line("Per-stop boardings:"));
+        for (stop, boardings, alightings, wait) in &stop_detail {
+            txt.add(Line(format!(
+                "  {}: {} boarded, {} alighted, {} total wait",
+                stop,
+                boardings,
+                alightings,
+                wait.minimal_tostring()
+            )));
+        }
+
+        if let Some((ref stops, _)) = self.new_route {
+            txt.add(Line(format!(
+                "Designing new route: {} stops chosen so far",
+                stops.len()
+            )));
+        }
+        if let Some((ref stops, _)) = self.new_route_headway {
+            txt.add(Line(format!(
+                "Designing new route: {} stops chosen, pick a headway",
+                stops.len()
+            )));
+        }
+
+        menu.set_info(ctx, txt);
+        None
+    }
+
+    pub fn draw(&self, _g: &mut GfxCtx) {}
+}
+
+fn median_headway(arrivals: &[Time]) -> Duration {
+    if arrivals.len() < 2 {
+        return Duration::ZERO;
+    }
+    let mut gaps: Vec<Duration> = arrivals.windows(2).map(|w| w[1] - w[0]).collect();
+    gaps.sort();
+    gaps[gaps.len() / 2]
+}
+
+// (total wait, median wait, 90th percentile wait) of every Person who boarded a bus on this
+// route.
+fn passenger_wait_stats(
+    analytics: &Analytics,
+    route: BusRouteID,
+) -> (Duration, Duration, Duration) {
+    let waits = analytics.get_passenger_waits(route);
+    if waits.is_empty() {
+        return (Duration::ZERO, Duration::ZERO, Duration::ZERO);
+    }
+    let total: Duration = waits.iter().fold(Duration::ZERO, |a, b| a + *b);
+    let mut sorted = waits.clone();
+    sorted.sort();
+    let p50 = sorted[sorted.len() / 2];
+    let p90 = sorted[(sorted.len() * 9) / 10];
+    (total, p50, p90)
+}
+
+// Boardings, alightings, and total wait time at each stop on the route, for the per-stop menu
+// breakdown.
+fn per_stop_detail(
+    analytics: &Analytics,
+    route: BusRouteID,
+) -> Vec<(BusStopID, usize, usize, Duration)> {
+    let alightings = analytics.get_alightings_per_stop(route);
+    analytics
+        .get_passenger_waits_per_stop(route)
+        .into_iter()
+        .map(|(stop, waits)| {
+            let wait = waits.iter().fold(Duration::ZERO, |a, b| a + *b);
+            let alighted = alightings.get(&stop).cloned().unwrap_or(0);
+            (stop, waits.len(), alighted, wait)
+        })
+        .collect()
+}
+
+// How many riders boarded this route in the window ending at `now`, replayed from an Analytics
+// (either the live sim's or the completed prebaked baseline's).
+fn windowed_boardings(
+    analytics: &Analytics,
+    route: BusRouteID,
+    now: Time,
+    horizon: Duration,
+) -> usize {
+    analytics
+        .get_boarding_times(route)
+        .into_iter()
+        .filter(|t| *t <= now && now - *t <= horizon)
+        .count()
+}
+
+// Prompts for the next stop to append to the route currently under design. The player cancels
+// the wizard (Escape) once they've picked the last stop.
+fn pick_new_route_stop(wiz: &mut Wizard, ctx: &mut EventCtx, ui: &UI) -> Option<BusStopID> {
+    let stops: Vec<(String, BusStopID)> = ui
+        .primary
+        .map
+        .all_bus_stops()
+        .keys()
+        .map(|id| (id.to_string(), *id))
+        .collect();
+    wiz.wrap(ctx).choose_something_no_keys(
+        "Add which stop to the new route?",
+        Box::new(move || stops.clone()),
+    )
+}
+
+// Prompts for the new route's headway, once every stop has been picked.
+fn pick_new_route_headway(wiz: &mut Wizard, ctx: &mut EventCtx) -> Option<usize> {
+    wiz.wrap(ctx).input_usize("Headway for the new route, in minutes?")
+}
+
+// Seeds the freshly designed route into the transit sim with the chosen headway. The menu keeps
+// reporting stats for route_name only; the new route's ridership and waits aren't shown.
+fn instantiate_new_route(stops: Vec<BusStopID>, headway: Duration, ui: &mut UI) {
+    ui.primary.sim.add_bus_route(stops, headway, &ui.primary.map);
+}
@@ -0,0 +1,208 @@
+use crate::game::Transition;
+use crate::sandbox::gameplay::{cmp_count_fewer, cmp_windowed_fewer, Score, State, WINDOW_LENGTH};
+use crate::ui::UI;
+use ezgui::{hotkey, Color, EventCtx, GfxCtx, Key, Line, ModalMenu, Text};
+use geom::{Duration, Time};
+use rand::SeedableRng;
+use rand_xorshift::XorShiftRng;
+use sim::{Analytics, PersonID};
+use std::collections::{BTreeMap, BTreeSet};
+
+// How long two agents must share a building or transit vehicle before exposure becomes possible.
+const DWELL_THRESHOLD: Duration = Duration::const_seconds(15.0 * 60.0);
+const EXPOSURE_CHANCE: f64 = 0.1;
+const INCUBATION_PERIOD: Duration = Duration::const_seconds(3.0 * 24.0 * 3600.0);
+const RECOVERY_PERIOD: Duration = Duration::const_seconds(7.0 * 24.0 * 3600.0);
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SEIRState {
+    Susceptible,
+    Exposed,
+    Infected,
+    Recovered,
+}
+
+// Tracks how long each pair of co-located people has been sharing a space, and the SEIR status
+// of every person seeded into the scenario.
+pub struct Pandemic {
+    status: BTreeMap<PersonID, SEIRState>,
+    transition_at: BTreeMap<PersonID, Time>,
+    // (PersonID, PersonID) with the lower ID first, mapped to when they started sharing a space.
+    colocated_since: BTreeMap<(PersonID, PersonID), Time>,
+    rng: XorShiftRng,
+    // Timestamp of every Exposed->Infected transition, replayed through a window filter the same
+    // way windowed_pandemic_infections replays the prebaked model's infection events, to show how
+    // fast the outbreak is spreading right now rather than just the cumulative total.
+    infection_times: Vec<Time>,
+    // Sim time of the last update_spread run, so it runs once per sim tick, not once per frame.
+    last_tick: Option<Time>,
+}
+
+impl Pandemic {
+    pub fn new(ctx: &mut EventCtx, ui: &UI) -> (ModalMenu, State) {
+        let mut status = BTreeMap::new();
+        for p in ui.primary.sim.get_all_people() {
+            status.insert(p, SEIRState::Susceptible);
+        }
+        // Seed patient zero.
+        if let Some(p) = status.keys().next().cloned() {
+            status.insert(p, SEIRState::Infected);
+        }
+
+        let menu = ModalMenu::new(
+            "Contain the Outbreak",
+            vec![vec![
+                (hotkey(Key::L), "show status breakdown"),
+                (hotkey(Key::Tab), "pick a challenge"),
+            ]],
+            ctx,
+        );
+        (
+            menu,
+            State::Pandemic(Pandemic {
+                status,
+                transition_at: BTreeMap::new(),
+                colocated_since: BTreeMap::new(),
+                rng: XorShiftRng::from_entropy(),
+                infection_times: Vec::new(),
+                last_tick: None,
+            }),
+        )
+    }
+
+    // Call every time the sim clock advances. Looks at who's currently sharing a building or
+    // transit vehicle, and runs the SEIR transitions.
+    fn update_spread(&mut self, ui: &UI, now: Time) {
+        let mut still_colocated = BTreeSet::new();
+        for occupants in ui.primary.sim.get_colocated_people() {
+            for i in 0..occupants.len() {
+                for j in (i + 1)..occupants.len() {
+                    let (a, b) = if occupants[i] < occupants[j] {
+                        (occupants[i], occupants[j])
+                    } else {
+                        (occupants[j], occupants[i])
+                    };
+                    still_colocated.insert((a, b));
+                    let since = *self.colocated_since.entry((a, b)).or_insert(now);
+                    if now - since >= DWELL_THRESHOLD {
+                        self.maybe_expose(a, b, now);
+                        self.maybe_expose(b, a, now);
+                    }
+                }
+            }
+        }
+        // Forget pairs that aren't sharing a space this tick, so a later reunion starts counting
+        // dwell time from scratch instead of from their first-ever encounter.
+        self.colocated_since
+            .retain(|pair, _| still_colocated.contains(pair));
+
+        for (person, state) in self.status.clone() {
+            let due = match self.transition_at.get(&person) {
+                Some(t) => *t <= now,
+                None => false,
+            };
+            if !due {
+                continue;
+            }
+            match state {
+                SEIRState::Exposed => {
+                    self.status.insert(person, SEIRState::Infected);
+                    self.transition_at.insert(person, now + RECOVERY_PERIOD);
+                    self.infection_times.push(now);
+                }
+                SEIRState::Infected => {
+                    self.status.insert(person, SEIRState::Recovered);
+                    self.transition_at.remove(&person);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn maybe_expose(&mut self, maybe_susceptible: PersonID, maybe_infected: PersonID, now: Time) {
+        use rand::Rng;
+
+        if self.status.get(&maybe_susceptible) != Some(&SEIRState::Susceptible) {
+            return;
+        }
+        if self.status.get(&maybe_infected) != Some(&SEIRState::Infected) {
+            return;
+        }
+        if self.rng.gen_bool(EXPOSURE_CHANCE) {
+            self.status.insert(maybe_susceptible, SEIRState::Exposed);
+            self.transition_at
+                .insert(maybe_susceptible, now + INCUBATION_PERIOD);
+        }
+    }
+
+    fn count(&self, want: SEIRState) -> usize {
+        self.status.values().filter(|s| **s == want).count()
+    }
+
+    // Currently active cases (Exposed or Infected), fewer is better. This rises and falls as the
+    // outbreak is contained or spreads, unlike the cumulative ever-infected count, which can only
+    // grow and would permanently lock in whatever the very first frame happened to read as the
+    // "personal best".
+    pub fn current_score(&self) -> Score {
+        Score::Count(self.count(SEIRState::Exposed) + self.count(SEIRState::Infected))
+    }
+
+    pub fn event(
+        &mut self,
+        ctx: &mut EventCtx,
+        ui: &mut UI,
+        menu: &mut ModalMenu,
+        prebaked: &Analytics,
+    ) -> Option<Transition> {
+        menu.event(ctx);
+        let now = ui.primary.sim.time();
+        if self.last_tick != Some(now) {
+            self.update_spread(ui, now);
+            self.last_tick = Some(now);
+        }
+
+        let mut txt = Text::prompt("Contain the Outbreak");
+        txt.add(Line(format!(
+            "Susceptible: {}",
+            self.count(SEIRState::Susceptible)
+        )));
+        txt.add(Line(format!("Exposed: {}", self.count(SEIRState::Exposed))).fg(Color::YELLOW));
+        txt.add(Line(format!("Infected: {}", self.count(SEIRState::Infected))).fg(Color::RED));
+        txt.add(Line(format!("Recovered: {}", self.count(SEIRState::Recovered))).fg(Color::GREEN));
+
+        // The baseline run never modeled disease spread directly, so the prebaked pandemic
+        // model's total ever-infected count is what we compare against.
+        let baseline_infections = prebaked.pandemic.total_ever_infected();
+        let now_infections = self.count(SEIRState::Infected) + self.count(SEIRState::Recovered);
+        let mut line = vec![Line("Total infections: "), Line(now_infections.to_string())];
+        line.push(cmp_count_fewer(now_infections, baseline_infections));
+        txt.add_appended(line);
+
+        let now_windowed_infections = self
+            .infection_times
+            .iter()
+            .filter(|t| **t <= now && now - **t <= WINDOW_LENGTH)
+            .count() as f64;
+        txt.add(cmp_windowed_fewer(
+            now_windowed_infections,
+            windowed_pandemic_infections(prebaked, now, WINDOW_LENGTH),
+        ));
+
+        menu.set_info(ctx, txt);
+        None
+    }
+
+    pub fn draw(&self, _g: &mut GfxCtx) {}
+}
+
+// How many people the prebaked pandemic model infected in the window ending at `now`, replaying
+// its timestamped infection events the same way windowed_gridlock_avg/windowed_finished_trips do
+// for their Analytics.
+fn windowed_pandemic_infections(analytics: &Analytics, now: Time, horizon: Duration) -> f64 {
+    analytics
+        .pandemic
+        .get_infection_times()
+        .into_iter()
+        .filter(|t| *t <= now && now - *t <= horizon)
+        .count() as f64
+}